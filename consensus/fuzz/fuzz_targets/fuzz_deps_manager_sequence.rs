@@ -0,0 +1,36 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use kaspa_consensus_core::api::ConsensusApi;
+use kaspa_consensus_fuzz::{arbitrary_blocks, new_fuzz_consensus};
+use libfuzzer_sys::fuzz_target;
+
+/// Replays a structured sequence of blocks -- including ones whose parents are still being
+/// processed or were themselves rejected -- to catch state-machine bugs in `deps_manager`'s
+/// buffering of blocks whose parents are still in flight. Submission is fire-and-forget against the async
+/// processing pipeline, mirroring how blocks actually arrive out of order over p2p; the only
+/// invariant under test is that the sequence drains without panicking or hanging.
+const MAX_BLOCKS_PER_RUN: usize = 64;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let blocks = arbitrary_blocks(&mut u, MAX_BLOCKS_PER_RUN);
+    if blocks.is_empty() {
+        return;
+    }
+
+    let consensus = new_fuzz_consensus();
+    let genesis = consensus.get_virtual_parents().into_iter().next().expect("fresh consensus has a genesis tip");
+
+    // Each block's parent pool grows as the sequence progresses, so later blocks may reference
+    // the hashes of any earlier ones, accepted or not. Blocks are always submitted after every
+    // block they reference, so deps_manager sees children whose parents are still in flight, but
+    // never a child submitted before its parent.
+    let mut accepted_pool = vec![genesis];
+    for arbitrary_block in blocks {
+        let block = arbitrary_block.to_block(&accepted_pool, 0, 0, &consensus);
+        let hash = block.hash();
+        let _ = consensus.validate_and_insert_block(block).virtual_state_task;
+        accepted_pool.push(hash);
+    }
+});
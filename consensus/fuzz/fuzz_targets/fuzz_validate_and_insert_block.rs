@@ -0,0 +1,21 @@
+#![no_main]
+
+use kaspa_consensus_core::api::ConsensusApi;
+use kaspa_consensus_fuzz::{new_fuzz_consensus, ArbitraryBlock};
+use libfuzzer_sys::fuzz_target;
+
+/// Feeds a single arbitrary, possibly-malformed block into a fresh consensus instance, driving
+/// `header_processor`, `body_processor` and `virtual_processor` on whatever bytes the fuzzer
+/// generated. The only invariant under test: every rejection must surface as a typed
+/// `ConsensusError`/`RuleError` from `validate_and_insert_block`, never a panic, index-out-of-bounds
+/// or unbounded allocation -- since these bytes model untrusted blocks arriving over p2p.
+fuzz_target!(|block: ArbitraryBlock| {
+    let consensus = new_fuzz_consensus();
+    let genesis = consensus.get_virtual_parents().into_iter().next().expect("fresh consensus has a genesis tip");
+    let block = block.to_block(&[genesis], 0, 0, &consensus);
+
+    // The returned task is dropped unawaited, so its result is not inspected: the block is still
+    // processed by the processor threads before shutdown, and a panic there is the bug, while a
+    // rejection is a perfectly valid outcome for malformed input.
+    let _ = consensus.validate_and_insert_block(block).virtual_state_task;
+});
@@ -0,0 +1,112 @@
+//! Shared helpers for the consensus fuzz targets: spinning up a throwaway, PoW-skipped
+//! `Consensus` and synthesizing arbitrary (possibly-malformed) blocks to feed into it.
+
+use arbitrary::{Arbitrary, Unstructured};
+use kaspa_consensus::{
+    config::ConfigBuilder,
+    consensus::Consensus,
+    params::DEVNET_PARAMS,
+    pipeline::ProcessingCounters,
+};
+use kaspa_consensus_core::{
+    api::ConsensusApi,
+    block::{Block, MutableBlock},
+    header::Header,
+};
+use kaspa_consensus_notify::root::ConsensusNotificationRoot;
+use kaspa_database::utils::{create_temp_db_with_parallelism, DbLifetime};
+use kaspa_hashes::Hash;
+use std::{ops::Deref, sync::Arc, thread::JoinHandle};
+
+/// A fresh, temp-DB-backed, PoW-skipped `Consensus` with its background header/body/virtual processor
+/// threads started (mirroring the `run_processors`/`shutdown` pairing `simpa` uses), so blocks
+/// submitted through it actually get drained and processed rather than queuing forever. Shuts
+/// the processors down on drop, so fuzz targets don't each have to remember to do it themselves.
+pub struct FuzzConsensus {
+    consensus: Arc<Consensus>,
+    handles: Option<Vec<JoinHandle<()>>>,
+    _lifetime: DbLifetime,
+}
+
+impl Deref for FuzzConsensus {
+    type Target = Consensus;
+
+    fn deref(&self) -> &Consensus {
+        &self.consensus
+    }
+}
+
+impl Drop for FuzzConsensus {
+    fn drop(&mut self) {
+        if let Some(handles) = self.handles.take() {
+            self.consensus.shutdown(handles);
+        }
+    }
+}
+
+/// Builds a [`FuzzConsensus`] so fuzzing time is spent on the structural and consensus-rule
+/// validation paths rather than grinding proof of work.
+pub fn new_fuzz_consensus() -> FuzzConsensus {
+    let (lifetime, db) = create_temp_db_with_parallelism(1);
+    let (sender, _receiver) = async_channel::unbounded();
+    let notification_root = Arc::new(ConsensusNotificationRoot::new(sender));
+    let config = Arc::new(ConfigBuilder::new(DEVNET_PARAMS).skip_proof_of_work().build());
+    let consensus =
+        Arc::new(Consensus::new(db, config, Arc::new(ProcessingCounters::default()), notification_root, Default::default()));
+    let handles = consensus.run_processors();
+    FuzzConsensus { consensus, handles: Some(handles), _lifetime: lifetime }
+}
+
+/// A structurally-arbitrary header/body pair synthesized by `arbitrary` from raw fuzzer bytes.
+/// Parents are picked from a bounded pool of already-submitted hashes (by index modulo pool size)
+/// rather than fully random hashes, so the fuzzer spends its budget exercising validation rules
+/// instead of exclusively hitting "unknown parent" rejections.
+#[derive(Debug, Arbitrary)]
+pub struct ArbitraryBlock {
+    parent_indices: Vec<u8>,
+    timestamp: u64,
+    nonce: u64,
+    blue_work: u64,
+    blue_score: u64,
+    num_coinbase_outputs: u8,
+}
+
+impl ArbitraryBlock {
+    /// Builds an immutable `Block` whose parents are drawn from `accepted_pool` (never empty,
+    /// since `genesis` is always index 0). The body is a single coinbase transaction with
+    /// `num_coinbase_outputs` outputs -- fuzzer-controlled, so body_processor's transaction-body
+    /// validation is actually exercised instead of only ever seeing an empty body.
+    pub fn to_block(&self, accepted_pool: &[Hash], version: u16, genesis_bits: u32, consensus: &Consensus) -> Block {
+        let parents = if self.parent_indices.is_empty() {
+            vec![accepted_pool[0]]
+        } else {
+            self.parent_indices.iter().map(|&i| accepted_pool[i as usize % accepted_pool.len()]).collect()
+        };
+        let header = Header::new_finalized(
+            version,
+            vec![parents],
+            Default::default(),
+            self.timestamp,
+            genesis_bits,
+            self.nonce,
+            0,
+            (self.blue_work as u128).into(),
+            self.blue_score,
+            Default::default(),
+        );
+        let coinbase = consensus.get_coinbase_transaction(self.num_coinbase_outputs as u64);
+        MutableBlock::new(header, vec![coinbase]).to_immutable()
+    }
+}
+
+/// Consumes up to `max` `ArbitraryBlock`s from `u`, stopping early once the bytes run out.
+pub fn arbitrary_blocks(u: &mut Unstructured, max: usize) -> Vec<ArbitraryBlock> {
+    let mut blocks = Vec::new();
+    while blocks.len() < max {
+        match ArbitraryBlock::arbitrary(u) {
+            Ok(block) => blocks.push(block),
+            Err(_) => break,
+        }
+    }
+    blocks
+}
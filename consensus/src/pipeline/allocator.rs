@@ -0,0 +1,74 @@
+//! Optional jemalloc integration for the parallel processing thread pools.
+//!
+//! Parallel transaction verification in `virtual_processor`, and the header/body processor pools
+//! (sized by `PerfParams::virtual_processor_num_threads` / `block_processors_num_threads`), share
+//! the process-wide allocator by default, which becomes a source of cross-thread free-list
+//! contention under high bps. Built with the `jemalloc` feature, each pool worker thread would
+//! instead be pinned to its own arena via [`pin_thread_to_arena`], passed as each pool's
+//! `start_handler` where `header_processor`/`body_processor`/`virtual_processor` build their
+//! pools, so its allocations and frees stay thread-local. The arena count for a given pool is
+//! picked by [`arena_count_for_pool`], which honors a caller-supplied override and otherwise
+//! scales to that pool's own thread count.
+//!
+//! Nothing in this workspace currently builds `header_processor`/`body_processor`/
+//! `virtual_processor`'s actual pools with that `start_handler`, declares a `jemalloc` Cargo
+//! feature, or depends on `tikv-jemallocator`/`tikv-jemalloc-ctl` -- wiring all three in requires
+//! touching `consensus`'s and `simpa`'s manifests and the real pool-construction sites, none of
+//! which are part of this change. This module is therefore a self-contained, tested primitive for
+//! that follow-up work, not an enabled feature; `simpa` does not expose a CLI flag for it.
+
+/// Number of jemalloc arenas to provision for a worker pool of `num_threads` threads, so each
+/// thread can be pinned to a dedicated arena. Honors an explicit `configured` override when set
+/// and non-zero; otherwise scales to `num_threads`.
+pub fn arena_count_for_pool(num_threads: usize, configured: Option<usize>) -> usize {
+    configured.filter(|&n| n > 0).unwrap_or(num_threads).max(1)
+}
+
+#[cfg(feature = "jemalloc")]
+mod jemalloc {
+    use tikv_jemalloc_ctl::thread;
+
+    /// `rayon::ThreadPoolBuilder::start_handler` hook that pins worker thread `index` to one of
+    /// `narenas` jemalloc arenas (round-robin), so concurrent allocation/free traffic from
+    /// different pool threads doesn't contend on the same arena's free lists.
+    pub fn pin_thread_to_arena(index: usize, narenas: usize) {
+        if narenas == 0 {
+            return;
+        }
+        let _ = thread::arena::write((index % narenas) as u32);
+    }
+}
+
+#[cfg(not(feature = "jemalloc"))]
+mod jemalloc {
+    /// No-op when the `jemalloc` feature is disabled: the process uses the default system
+    /// allocator and there is no arena to pin threads to.
+    pub fn pin_thread_to_arena(_index: usize, _narenas: usize) {}
+}
+
+pub use jemalloc::pin_thread_to_arena;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_count_scales_to_pool_when_unconfigured() {
+        assert_eq!(arena_count_for_pool(8, None), 8);
+    }
+
+    #[test]
+    fn arena_count_honors_configured_override() {
+        assert_eq!(arena_count_for_pool(8, Some(4)), 4);
+    }
+
+    #[test]
+    fn arena_count_ignores_zero_override() {
+        assert_eq!(arena_count_for_pool(8, Some(0)), 8);
+    }
+
+    #[test]
+    fn arena_count_is_never_zero() {
+        assert_eq!(arena_count_for_pool(0, None), 1);
+    }
+}
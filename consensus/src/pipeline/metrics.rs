@@ -0,0 +1,168 @@
+//! OpenMetrics text-format export for [`ProcessingCounters`].
+//!
+//! The `monitor` module already turns the counters into periodic log lines by diffing successive
+//! snapshots; this module serves the same counters (plus the rates `monitor` derives) over plain
+//! HTTP, so a running node -- or a simulator run -- can be scraped by standard metrics tooling
+//! instead of only read from logs.
+//!
+//! `chain_disqualified_counts`/`reorg_depths` are deliberately left out of [`render`]: they're
+//! only ever populated by `simpa`'s own event loop (see `ProcessingCounters::record_reorg`'s doc
+//! comment) since `virtual_processor` doesn't call `record_reorg` yet, so on a real node they
+//! would always read zero. Exporting a `kaspa_chain_disqualified_total` series here would look
+//! like a live node metric while actually being simulator-only; `simpa`'s own `print_reorg_stats`
+//! log line is the right place for it until the node-side hook exists.
+
+use super::{ProcessingCounters, ProcessingCountersSnapshot};
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Instant,
+};
+
+/// The three processing stages `ProcessingCounters` distinguishes, used as a metric label so a
+/// single `kaspa_processed_blocks_total` series covers all of them.
+#[derive(Clone, Copy)]
+enum Stage {
+    Header,
+    Body,
+    Chain,
+}
+
+impl Stage {
+    fn label(self) -> &'static str {
+        match self {
+            Stage::Header => "header",
+            Stage::Body => "body",
+            Stage::Chain => "chain",
+        }
+    }
+}
+
+/// Blocks/txs/mass per second, derived from the diff between two snapshots a known duration apart.
+struct Rates {
+    blocks_per_sec: f64,
+    txs_per_sec: f64,
+    mass_per_sec: f64,
+}
+
+/// Renders `snapshot` and the optional derived `rates` (absent on the very first scrape, since
+/// there is no prior snapshot to diff against) as OpenMetrics text exposition format.
+fn render(snapshot: &ProcessingCountersSnapshot, rates: Option<Rates>) -> String {
+    let mut out = String::new();
+
+    out.push_str("# TYPE kaspa_blocks_submitted_total counter\n");
+    out.push_str(&format!("kaspa_blocks_submitted_total {}\n", snapshot.blocks_submitted));
+
+    out.push_str("# TYPE kaspa_processed_blocks_total counter\n");
+    for (stage, count) in
+        [(Stage::Header, snapshot.header_counts), (Stage::Body, snapshot.body_counts), (Stage::Chain, snapshot.chain_block_counts)]
+    {
+        out.push_str(&format!("kaspa_processed_blocks_total{{stage=\"{}\"}} {count}\n", stage.label()));
+    }
+
+    out.push_str("# TYPE kaspa_txs_total counter\n");
+    out.push_str(&format!("kaspa_txs_total {}\n", snapshot.txs_counts));
+
+    out.push_str("# TYPE kaspa_mass_total counter\n");
+    out.push_str(&format!("kaspa_mass_total {}\n", snapshot.mass_counts));
+
+    if let Some(rates) = rates {
+        out.push_str("# TYPE kaspa_blocks_per_second gauge\n");
+        out.push_str(&format!("kaspa_blocks_per_second {:.4}\n", rates.blocks_per_sec));
+        out.push_str("# TYPE kaspa_txs_per_second gauge\n");
+        out.push_str(&format!("kaspa_txs_per_second {:.4}\n", rates.txs_per_sec));
+        out.push_str("# TYPE kaspa_mass_per_second gauge\n");
+        out.push_str(&format!("kaspa_mass_per_second {:.4}\n", rates.mass_per_sec));
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Serves `GET /metrics` in OpenMetrics text format on a dedicated background thread, bound to
+/// `bind_addr`. Rates are recomputed on every scrape from the diff against the previously-served
+/// snapshot, mirroring the snapshot-diffing `monitor` already does on its own timer. Takes just the
+/// shared `ProcessingCounters`, so any binary holding one -- the node or `simpa` -- can start this
+/// behind its own opt-in config/CLI flag; currently only `simpa`'s `--metrics-addr` does.
+pub fn spawn_metrics_server(counters: Arc<ProcessingCounters>, bind_addr: &str) -> std::io::Result<thread::JoinHandle<()>> {
+    let listener = TcpListener::bind(bind_addr)?;
+    kaspa_core::info!("Metrics exporter listening on http://{}/metrics", bind_addr);
+
+    Ok(thread::spawn(move || {
+        let mut prev: Option<(ProcessingCountersSnapshot, Instant)> = None;
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let snapshot = counters.snapshot();
+            let now = Instant::now();
+            let rates = prev.as_ref().map(|(prev_snapshot, prev_instant)| {
+                let elapsed = now.duration_since(*prev_instant).as_secs_f64().max(f64::EPSILON);
+                let diff = &snapshot - prev_snapshot;
+                Rates {
+                    blocks_per_sec: diff.chain_block_counts as f64 / elapsed,
+                    txs_per_sec: diff.txs_counts as f64 / elapsed,
+                    mass_per_sec: diff.mass_counts as f64 / elapsed,
+                }
+            });
+            let body = render(&snapshot, rates);
+            prev = Some((snapshot, now));
+            serve(stream, &body);
+        }
+    }))
+}
+
+/// Writes `body` as a minimal `200 OK` response. The request itself is drained and ignored since
+/// this endpoint serves exactly one resource regardless of path or method.
+fn serve(mut stream: TcpStream, body: &str) {
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request);
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(blocks_submitted: u64, chain_disqualified_counts: u64) -> ProcessingCountersSnapshot {
+        ProcessingCountersSnapshot {
+            blocks_submitted,
+            header_counts: 1,
+            dep_counts: 0,
+            body_counts: 2,
+            txs_counts: 3,
+            chain_block_counts: 4,
+            mass_counts: 5,
+            chain_disqualified_counts,
+            reorg_depths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn render_without_rates_omits_gauges() {
+        let body = render(&snapshot(10, 1), None);
+        assert!(body.contains("kaspa_blocks_submitted_total 10\n"));
+        assert!(body.contains("kaspa_processed_blocks_total{stage=\"header\"} 1\n"));
+        assert!(body.contains("kaspa_processed_blocks_total{stage=\"body\"} 2\n"));
+        assert!(body.contains("kaspa_processed_blocks_total{stage=\"chain\"} 4\n"));
+        // chain_disqualified_counts is simulator-only (see module doc) and must not be exported.
+        assert!(!body.contains("kaspa_chain_disqualified_total"));
+        assert!(!body.contains("kaspa_blocks_per_second"));
+        assert!(body.trim_end().ends_with("# EOF"));
+    }
+
+    #[test]
+    fn render_with_rates_includes_gauges() {
+        let rates = Rates { blocks_per_sec: 1.5, txs_per_sec: 2.5, mass_per_sec: 3.5 };
+        let body = render(&snapshot(10, 0), Some(rates));
+        assert!(body.contains("kaspa_blocks_per_second 1.5000\n"));
+        assert!(body.contains("kaspa_txs_per_second 2.5000\n"));
+        assert!(body.contains("kaspa_mass_per_second 3.5000\n"));
+    }
+}
@@ -1,10 +1,13 @@
+pub mod allocator;
 pub mod body_processor;
 pub mod deps_manager;
 pub mod header_processor;
+pub mod metrics;
 pub mod monitor;
 pub mod pruning_processor;
 pub mod virtual_processor;
 
+use parking_lot::Mutex;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 #[derive(Default)]
@@ -16,9 +19,28 @@ pub struct ProcessingCounters {
     pub txs_counts: AtomicU64,
     pub chain_block_counts: AtomicU64,
     pub mass_counts: AtomicU64,
+    /// Cumulative number of blocks rolled back off the selected chain across all reorgs so far
+    pub chain_disqualified_counts: AtomicU64,
+    /// Append-only log of reorg depths (number of blocks rolled back from the previous virtual
+    /// selected tip to the new common ancestor), one entry per reorg. Used to derive per-run
+    /// histogram statistics (max/mean/p99) without requiring a dedicated histogram type.
+    reorg_depths: Mutex<Vec<u64>>,
 }
 
 impl ProcessingCounters {
+    /// Records a selected-parent chain reorganization of the given depth. Intended to be called
+    /// wherever virtual resolves to a new selected tip that is not a descendant of the previous
+    /// one; currently only `simpa`'s simulator event loop does so (see
+    /// `KaspaNetworkSimulator::record_reorg_if_any`) -- `virtual_processor` does not yet call this,
+    /// so reorgs on a real node are not counted here.
+    pub fn record_reorg(&self, depth: u64) {
+        if depth == 0 {
+            return;
+        }
+        self.chain_disqualified_counts.fetch_add(depth, Ordering::Relaxed);
+        self.reorg_depths.lock().push(depth);
+    }
+
     pub fn snapshot(&self) -> ProcessingCountersSnapshot {
         ProcessingCountersSnapshot {
             blocks_submitted: self.blocks_submitted.load(Ordering::Relaxed),
@@ -28,6 +50,8 @@ impl ProcessingCounters {
             txs_counts: self.txs_counts.load(Ordering::Relaxed),
             chain_block_counts: self.chain_block_counts.load(Ordering::Relaxed),
             mass_counts: self.mass_counts.load(Ordering::Relaxed),
+            chain_disqualified_counts: self.chain_disqualified_counts.load(Ordering::Relaxed),
+            reorg_depths: self.reorg_depths.lock().clone(),
         }
     }
 }
@@ -41,6 +65,34 @@ pub struct ProcessingCountersSnapshot {
     pub txs_counts: u64,
     pub chain_block_counts: u64,
     pub mass_counts: u64,
+    pub chain_disqualified_counts: u64,
+    /// Reorg depths recorded up to this snapshot, in recording order
+    pub reorg_depths: Vec<u64>,
+}
+
+impl ProcessingCountersSnapshot {
+    /// Max/mean/99th-percentile reorg depth observed over the depths in this snapshot.
+    /// Typically called on the diff between two snapshots to get per-run statistics.
+    pub fn reorg_depth_stats(&self) -> ReorgDepthStats {
+        if self.reorg_depths.is_empty() {
+            return ReorgDepthStats::default();
+        }
+        let mut sorted = self.reorg_depths.clone();
+        sorted.sort_unstable();
+        let count = sorted.len();
+        let max_depth = *sorted.last().unwrap();
+        let mean_depth = sorted.iter().sum::<u64>() as f64 / count as f64;
+        let p99_depth = sorted[((count - 1) as f64 * 0.99).round() as usize];
+        ReorgDepthStats { count: count as u64, max_depth, mean_depth, p99_depth }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ReorgDepthStats {
+    pub count: u64,
+    pub max_depth: u64,
+    pub mean_depth: f64,
+    pub p99_depth: u64,
 }
 
 impl core::ops::Sub for &ProcessingCountersSnapshot {
@@ -55,6 +107,51 @@ impl core::ops::Sub for &ProcessingCountersSnapshot {
             txs_counts: self.txs_counts - rhs.txs_counts,
             chain_block_counts: self.chain_block_counts - rhs.chain_block_counts,
             mass_counts: self.mass_counts - rhs.mass_counts,
+            chain_disqualified_counts: self.chain_disqualified_counts - rhs.chain_disqualified_counts,
+            // `rhs` is always an earlier (shorter-or-equal) snapshot of the same append-only log,
+            // so the depths recorded since `rhs` are exactly the suffix past its length.
+            reorg_depths: self.reorg_depths[rhs.reorg_depths.len()..].to_vec(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot_with_depths(reorg_depths: Vec<u64>) -> ProcessingCountersSnapshot {
+        ProcessingCountersSnapshot {
+            blocks_submitted: 0,
+            header_counts: 0,
+            dep_counts: 0,
+            body_counts: 0,
+            txs_counts: 0,
+            chain_block_counts: 0,
+            mass_counts: 0,
+            chain_disqualified_counts: 0,
+            reorg_depths,
+        }
+    }
+
+    #[test]
+    fn reorg_depth_stats_empty() {
+        let stats = snapshot_with_depths(vec![]).reorg_depth_stats();
+        assert_eq!(stats, ReorgDepthStats::default());
+    }
+
+    #[test]
+    fn reorg_depth_stats_single() {
+        let stats = snapshot_with_depths(vec![5]).reorg_depth_stats();
+        assert_eq!(stats, ReorgDepthStats { count: 1, max_depth: 5, mean_depth: 5.0, p99_depth: 5 });
+    }
+
+    #[test]
+    fn reorg_depth_stats_multiple_unsorted() {
+        let stats = snapshot_with_depths(vec![3, 1, 100, 2]).reorg_depth_stats();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.max_depth, 100);
+        assert_eq!(stats.mean_depth, (1 + 2 + 3 + 100) as f64 / 4.0);
+        // sorted: [1, 2, 3, 100], p99 index = round(3 * 0.99) = 3 -> 100
+        assert_eq!(stats.p99_depth, 100);
+    }
+}
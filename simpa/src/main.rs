@@ -13,6 +13,7 @@ use kaspa_consensus::{
         relations::RelationsStoreReader,
     },
     params::{Params, Testnet11Bps, DEVNET_PARAMS, TESTNET11_PARAMS},
+    pipeline::{metrics::spawn_metrics_server, ProcessingCounters},
 };
 use kaspa_consensus_core::{
     api::ConsensusApi, block::Block, blockstatus::BlockStatus, config::bps::calculate_ghostdag_k, errors::block::BlockProcessResult,
@@ -90,6 +91,24 @@ struct Args {
     /// Use testnet-11 consensus params
     #[arg(long, default_value_t = false)]
     testnet11: bool,
+
+    /// Fraction of hashrate controlled by a colluding attacker coalition which mines privately
+    /// and withholds its blocks until the public DAG's blue work threatens to overtake it
+    /// (classic block-withholding/selfish-mining attack, recast for GHOSTDAG). Rounded to a whole
+    /// number of the `--miners` (at least one attacker and one honest miner); each attacker miner
+    /// withholds on its own private branch
+    #[arg(long)]
+    attacker_hashrate: Option<f64>,
+
+    /// Maximum number of blocks each attacker miner withholds before being forced to release
+    /// its private branch. Only takes effect alongside `--attacker-hashrate`
+    #[arg(long, default_value_t = 10)]
+    attacker_withhold: u64,
+
+    /// Address to serve live OpenMetrics stats on (e.g. 127.0.0.1:9090, scraped at /metrics), so a run can be
+    /// observed with standard metrics tooling while it's in progress. Disabled unless specified
+    #[arg(long)]
+    metrics_addr: Option<String>,
 }
 
 fn main() {
@@ -127,19 +146,31 @@ fn main() {
     let config = Arc::new(builder.build());
 
     // Load an existing consensus or run the simulation
-    let (consensus, _lifetime) = if let Some(input_dir) = args.input_dir {
+    let (consensus, _lifetime, processing_counters, honest_hashes) = if let Some(input_dir) = args.input_dir {
         let (lifetime, db) = load_existing_db(input_dir, num_cpus::get());
         let (dummy_notification_sender, _) = unbounded();
         let notification_root = Arc::new(ConsensusNotificationRoot::new(dummy_notification_sender));
-        let consensus = Arc::new(Consensus::new(db, config.clone(), Default::default(), notification_root, Default::default()));
-        (consensus, lifetime)
+        let processing_counters = Arc::new(ProcessingCounters::default());
+        spawn_metrics_server_if_configured(&args.metrics_addr, &processing_counters);
+        let consensus =
+            Arc::new(Consensus::new(db, config.clone(), processing_counters.clone(), notification_root, Default::default()));
+        // A loaded DB carries no record of which miner produced each block, so honest-block
+        // attribution (see `print_attacker_stats`) isn't available for this run.
+        (consensus, lifetime, processing_counters, None)
     } else {
         let until = if args.target_blocks.is_none() { config.genesis.timestamp + args.sim_time * 1000 } else { u64::MAX }; // milliseconds
         let mut sim = KaspaNetworkSimulator::new(args.delay, args.bps, args.target_blocks, config.clone(), args.output_dir);
-        let (consensus, handles, lifetime) = sim.init(args.miners, args.tpb).run(until);
+        if let Some(attacker_hashrate) = args.attacker_hashrate {
+            sim = sim.with_attacker(attacker_hashrate, args.attacker_withhold);
+        }
+        sim.init(args.miners, args.tpb);
+        let processing_counters = sim.processing_counters();
+        spawn_metrics_server_if_configured(&args.metrics_addr, &processing_counters);
+        let (consensus, handles, lifetime) = sim.run(until);
         consensus.shutdown(handles);
-        (consensus, lifetime)
+        (consensus, lifetime, processing_counters, Some(sim.honest_block_hashes().clone()))
     };
+    print_reorg_stats(&processing_counters);
 
     if args.test_pruning {
         drop(consensus);
@@ -152,7 +183,7 @@ fn main() {
     let notification_root = Arc::new(ConsensusNotificationRoot::new(dummy_notification_sender));
     let consensus2 = Arc::new(Consensus::new(db2, config.clone(), Default::default(), notification_root, Default::default()));
     let handles2 = consensus2.run_processors();
-    validate(&consensus, &consensus2, &config, args.delay, args.bps);
+    validate(&consensus, &consensus2, &config, args.delay, args.bps, args.attacker_hashrate, honest_hashes.as_ref());
     consensus2.shutdown(handles2);
     drop(consensus);
 }
@@ -221,10 +252,19 @@ fn apply_args_to_perf_params(args: &Args, perf_params: &mut PerfParams) {
 }
 
 #[tokio::main]
-async fn validate(src_consensus: &Consensus, dst_consensus: &Consensus, params: &Params, delay: f64, bps: f64) {
+async fn validate(
+    src_consensus: &Consensus,
+    dst_consensus: &Consensus,
+    params: &Params,
+    delay: f64,
+    bps: f64,
+    attacker_hashrate: Option<f64>,
+    honest_hashes: Option<&BlockHashSet>,
+) {
     let hashes = topologically_ordered_hashes(src_consensus, params.genesis.hash);
     let num_blocks = hashes.len();
     let num_txs = print_stats(src_consensus, &hashes, delay, bps, params.ghostdag_k);
+    print_attacker_stats(src_consensus, &hashes, attacker_hashrate, honest_hashes);
     info!("Validating {num_blocks} blocks with {num_txs} transactions overall...");
     let start = std::time::Instant::now();
     let chunks = hashes.into_iter().chunks(1000);
@@ -303,3 +343,51 @@ fn print_stats(src_consensus: &Consensus, hashes: &[Hash], delay: f64, bps: f64,
     info!("[Average stats of generated DAG] blues: {blues_mean}, reds: {reds_mean}, parents: {parents_mean}, txs: {txs_mean}");
     num_txs
 }
+
+/// Starts the OpenMetrics exporter on `addr` if the `--metrics-addr` flag was given, so this run
+/// (a simulation or a DB loaded via `--input-dir`) can be scraped live by standard metrics tooling.
+fn spawn_metrics_server_if_configured(addr: &Option<String>, processing_counters: &Arc<ProcessingCounters>) {
+    let Some(addr) = addr else { return };
+    spawn_metrics_server(processing_counters.clone(), addr).unwrap_or_else(|e| panic!("failed to bind metrics exporter to {addr}: {e}"));
+}
+
+/// Reports selected-chain reorg depth statistics accumulated over the run, which characterizes
+/// confirmation-time safety for the configured `--bps`/`--delay` combination.
+fn print_reorg_stats(processing_counters: &ProcessingCounters) {
+    let stats = processing_counters.snapshot().reorg_depth_stats();
+    if stats.count == 0 {
+        info!("[Reorg stats] no selected-chain reorgs observed during this run");
+        return;
+    }
+    info!(
+        "[Reorg stats] {} reorgs observed, max depth: {}, mean depth: {:.2}, p99 depth: {}",
+        stats.count, stats.max_depth, stats.mean_depth, stats.p99_depth
+    );
+}
+
+/// Reports the fraction of *honest*-mined blocks that are red (orphaned) in the mergeset of at
+/// least one block of the DAG. Reds are collected from every block, not only from the selected
+/// chain, so a block that is red in an off-chain block's mergeset counts as excluded. This -- when an attacker coalition was configured via `--attacker-hashrate` -- is the actual
+/// cost the withholding attack imposed on honest miners, letting us empirically confirm GHOSTDAG's
+/// resistance to it at the configured bps*delay product. `honest_hashes` is `None` when the DAG
+/// was loaded via `--input-dir`, since a loaded DB carries no record of which miner mined what.
+fn print_attacker_stats(src_consensus: &Consensus, hashes: &[Hash], attacker_hashrate: Option<f64>, honest_hashes: Option<&BlockHashSet>) {
+    let Some(attacker_hashrate) = attacker_hashrate else { return };
+    let Some(honest_hashes) = honest_hashes else {
+        info!("[Attacker hashrate={attacker_hashrate}] no honest-block attribution available for a DAG loaded via --input-dir");
+        return;
+    };
+    // A block can appear in more than one descendant's mergeset_reds, so collect the distinct
+    // set of excluded blocks rather than summing per-block counts (which double-counts and can
+    // push the "fraction" above 1).
+    let mut reds = BlockHashSet::new();
+    for &h in hashes {
+        reds.extend(src_consensus.ghostdag_primary_store.get_data(h).unwrap().mergeset_reds.iter().copied());
+    }
+    let honest_total = hashes.iter().filter(|h| honest_hashes.contains(*h)).count();
+    let honest_excluded = hashes.iter().filter(|h| honest_hashes.contains(*h) && reds.contains(*h)).count();
+    let excluded_fraction = if honest_total == 0 { 0.0 } else { honest_excluded as f64 / honest_total as f64 };
+    info!(
+        "[Attacker hashrate={attacker_hashrate}] excluded (orphaned/red) fraction of honest blocks: {excluded_fraction:.4} ({honest_excluded}/{honest_total})"
+    );
+}
@@ -0,0 +1,478 @@
+use futures::executor::block_on;
+use kaspa_consensus::{
+    consensus::Consensus, model::stores::ghostdag::GhostdagStoreReader, params::Params, pipeline::ProcessingCounters,
+};
+use kaspa_consensus_core::{
+    api::ConsensusApi,
+    block::{Block, MutableBlock},
+    config::Config,
+    header::Header,
+    BlockHashSet,
+};
+use kaspa_consensus_notify::root::ConsensusNotificationRoot;
+use kaspa_core::{debug, info, task::tick::TickService};
+use kaspa_database::utils::{create_temp_db_with_parallelism, DbLifetime};
+use kaspa_hashes::Hash;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Exp};
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc, thread::JoinHandle};
+
+/// Configuration for attacker miners mounting a block-withholding (selfish-mining) attack: each
+/// attacker miner mines privately on its own hidden branch and only releases it once the public
+/// DAG's accumulated blue work threatens to overtake it.
+#[derive(Clone, Copy, Debug)]
+pub struct AttackerConfig {
+    /// Fraction of the configured miners (by hashrate) that collude in the withholding attack
+    pub hashrate_fraction: f64,
+    /// Maximum number of blocks the coalition withholds before it is forced to release
+    pub withhold_limit: u64,
+}
+
+/// Per-miner mining strategy. Honest miners always mine on the DAG they observe through the
+/// shared consensus; attacker-coalition miners mine on a private branch that is hidden from
+/// the rest of the network until [`AttackerState::release`] is triggered.
+enum MinerStrategy {
+    Honest,
+    Attacker,
+}
+
+/// Tracks the hidden branch being built by one attacker miner and the blocks it has withheld from
+/// the honest network so far. Each attacker miner holds its own state; attacker miners do not
+/// share or build on each other's withheld blocks.
+struct AttackerState {
+    /// Tips of the privately-mined branch, rooted at the public selected tip observed when the
+    /// attack started (or last released). Never relayed to other miners until release.
+    private_tips: Vec<Hash>,
+    /// Blue work of `private_tips`' common root, i.e. the public blue work the private branch
+    /// started from. Withheld blocks are never inserted into `consensus`, so there is no
+    /// ghostdag data to read their blue work back from -- instead each withheld block is assumed
+    /// to add `block_work` (a real per-block blue-work delta sampled from an inserted block, see
+    /// `KaspaNetworkSimulator::block_work`) on top of this root, which holds as long as the
+    /// private branch itself has no reds (true for a single withholding chain).
+    root_blue_work: u128,
+    /// Blocks withheld so far, in mining order, ready to be released in topological (blue work) order
+    withheld: Vec<Block>,
+    withhold_limit: u64,
+}
+
+impl AttackerState {
+    fn new(root: Hash, root_blue_work: u128, withhold_limit: u64) -> Self {
+        Self { private_tips: vec![root], root_blue_work, withheld: Vec::new(), withhold_limit }
+    }
+
+    /// Blue work of the tip of the private branch, estimated from the root plus `block_work` per
+    /// withheld block (see `root_blue_work`).
+    fn private_blue_work(&self, block_work: u128) -> u128 {
+        self.root_blue_work + self.withheld.len() as u128 * block_work
+    }
+
+    fn should_release(&self, public_blue_work: u128, block_work: u128) -> bool {
+        // Release as soon as the withheld lead is about to be overtaken, or once we hit the
+        // configured withholding cap -- mirrors the classic selfish-mining release condition.
+        self.withheld.len() as u64 >= self.withhold_limit || public_blue_work + block_work >= self.private_blue_work(block_work)
+    }
+
+    /// Drains the withheld blocks in topological (blue work ascending) order for release, and
+    /// re-roots the (now empty) private branch at the given public tip so mining can resume.
+    fn release(&mut self, new_root: Hash, new_root_blue_work: u128) -> Vec<Block> {
+        self.withheld.sort_by_key(|b| b.header.blue_work);
+        self.private_tips = vec![new_root];
+        self.root_blue_work = new_root_blue_work;
+        std::mem::take(&mut self.withheld)
+    }
+}
+
+/// A single simulated miner: owns a mining strategy and schedules its next block via a Poisson
+/// process whose rate is proportional to its share of the network hashrate.
+struct Miner {
+    id: u64,
+    hashrate_fraction: f64,
+    strategy: MinerStrategy,
+    attacker_state: Option<AttackerState>,
+}
+
+/// Discrete-event network simulator driving a [`Consensus`] instance by having simulated miners
+/// submit blocks at Poisson-distributed intervals, honest miners building on the observed virtual
+/// tips and attacker-coalition miners optionally mining privately and withholding blocks.
+pub struct KaspaNetworkSimulator {
+    delay: f64,
+    bps: f64,
+    target_blocks: Option<u64>,
+    config: Arc<Config>,
+    output_dir: Option<String>,
+    attacker: Option<AttackerConfig>,
+    rng: SmallRng,
+
+    consensus: Option<Arc<Consensus>>,
+    miners: Vec<Miner>,
+    target_txs_per_block: u64,
+    lifetime: Option<DbLifetime>,
+    processing_counters: Arc<ProcessingCounters>,
+    /// Hashes of blocks mined by an honest miner, tagged at mining time (before an attacker's
+    /// withheld blocks are ever inserted), so callers can isolate the honest-only subset of the
+    /// DAG -- e.g. to measure what the attack actually cost honest miners -- instead of treating
+    /// every inserted block (including the attacker's own orphaned ones) as "honest".
+    honest_hashes: BlockHashSet,
+    /// Real per-block GHOSTDAG blue-work delta for this run's (fixed, since PoW is skipped)
+    /// difficulty, sampled the first time an inserted block's blue work can be diffed against its
+    /// selected parent's (see [`Self::sample_block_work`]). `None` until that first sample lands,
+    /// since no block has been inserted yet.
+    block_work: Option<u128>,
+}
+
+impl KaspaNetworkSimulator {
+    pub fn new(delay: f64, bps: f64, target_blocks: Option<u64>, config: Arc<Config>, output_dir: Option<String>) -> Self {
+        Self {
+            delay,
+            bps,
+            target_blocks,
+            config,
+            output_dir,
+            attacker: None,
+            rng: SmallRng::from_entropy(),
+            consensus: None,
+            miners: Vec::new(),
+            target_txs_per_block: 0,
+            lifetime: None,
+            processing_counters: Arc::new(ProcessingCounters::default()),
+            honest_hashes: BlockHashSet::new(),
+            block_work: None,
+        }
+    }
+
+    /// Shared reference to the processing counters of the underlying consensus, including
+    /// selected-chain reorg depth instrumentation. Created in [`Self::new`] and handed to the
+    /// consensus that [`Self::init`] builds.
+    pub fn processing_counters(&self) -> Arc<ProcessingCounters> {
+        self.processing_counters.clone()
+    }
+
+    /// Hashes of blocks mined by an honest miner during [`Self::run`], as opposed to blocks the
+    /// attacker coalition mined (whether released or force-released at the end of the run).
+    pub fn honest_block_hashes(&self) -> &BlockHashSet {
+        &self.honest_hashes
+    }
+
+    /// Carves out a fraction of the miner pool into attacker miners that mine privately and each
+    /// withhold up to `withhold_limit` blocks before releasing their hidden branch. [`Self::init`]
+    /// rounds the fraction to a whole number of miners, keeping at least one attacker and one
+    /// honest miner; each attacker miner withholds on its own private branch.
+    pub fn with_attacker(mut self, hashrate_fraction: f64, withhold_limit: u64) -> Self {
+        assert!((0.0..1.0).contains(&hashrate_fraction), "attacker hashrate fraction must be in [0, 1)");
+        self.attacker = Some(AttackerConfig { hashrate_fraction, withhold_limit });
+        self
+    }
+
+    pub fn init(&mut self, num_miners: u64, target_txs_per_block: u64) -> &mut Self {
+        let (lifetime, db) = create_temp_db_with_parallelism(num_cpus::get());
+        let (dummy_notification_sender, _) = async_channel::unbounded();
+        let notification_root = Arc::new(ConsensusNotificationRoot::new(dummy_notification_sender));
+        let consensus = Arc::new(Consensus::new(
+            db,
+            self.config.clone(),
+            self.processing_counters.clone(),
+            notification_root,
+            Default::default(),
+        ));
+        self.lifetime = Some(lifetime);
+
+        let genesis = self.config.genesis.hash;
+        let attacker_miners = self.attacker.map(|a| {
+            assert!(
+                num_miners >= 2,
+                "--attacker-hashrate requires --miners >= 2 so at least one honest miner remains to attack"
+            );
+            // Reserve at least one honest miner: an all-attacker pool leaves nothing for
+            // `print_attacker_stats`'s honest-block attribution to compare against.
+            ((a.hashrate_fraction * num_miners as f64).round().max(1.0) as u64).min(num_miners - 1)
+        }).unwrap_or(0);
+        self.miners = (0..num_miners)
+            .map(|id| {
+                if id < attacker_miners {
+                    let withhold_limit = self.attacker.unwrap().withhold_limit;
+                    Miner {
+                        id,
+                        hashrate_fraction: 1.0 / num_miners as f64,
+                        strategy: MinerStrategy::Attacker,
+                        attacker_state: Some(AttackerState::new(genesis, 0, withhold_limit)),
+                    }
+                } else {
+                    Miner { id, hashrate_fraction: 1.0 / num_miners as f64, strategy: MinerStrategy::Honest, attacker_state: None }
+                }
+            })
+            .collect();
+        self.target_txs_per_block = target_txs_per_block;
+        self.consensus = Some(consensus);
+        self
+    }
+
+    pub fn run(&mut self, until: u64) -> (Arc<Consensus>, Vec<JoinHandle<()>>, DbLifetime) {
+        let consensus = self.consensus.clone().expect("init must be called before run");
+        let handles = consensus.run_processors();
+
+        let exp = Exp::new(self.bps).unwrap();
+        let mut heap: BinaryHeap<Reverse<(u64, u64)>> = BinaryHeap::new();
+        for miner in &self.miners {
+            let wait = (exp.sample(&mut self.rng) * 1000.0 / miner.hashrate_fraction) as u64;
+            heap.push(Reverse((wait, miner.id)));
+        }
+
+        let mut now = 0u64;
+        let mut num_blocks = 0u64;
+        let mut selected_tip = Self::selected_tip(&consensus);
+        while now < until && self.target_blocks.map_or(true, |t| num_blocks < t) {
+            let Reverse((time, miner_id)) = heap.pop().expect("there is always at least one scheduled miner");
+            now = time;
+            let miner = &self.miners[miner_id as usize];
+
+            let parents = match &miner.strategy {
+                MinerStrategy::Honest => consensus.get_virtual_parents().into_iter().collect::<Vec<_>>(),
+                MinerStrategy::Attacker => miner.attacker_state.as_ref().unwrap().private_tips.clone(),
+            };
+
+            let block = self.mine_block(&consensus, parents, now);
+            num_blocks += 1;
+
+            let miner = &mut self.miners[miner_id as usize];
+            match &mut miner.strategy {
+                MinerStrategy::Honest => {
+                    let hash = block.hash();
+                    self.honest_hashes.insert(hash);
+                    Self::insert_block(&consensus, block);
+                    Self::sample_block_work(&mut self.block_work, &consensus, hash);
+                }
+                MinerStrategy::Attacker => {
+                    let hash = block.hash();
+                    let state = miner.attacker_state.as_mut().unwrap();
+                    state.private_tips = vec![hash];
+                    state.withheld.push(block);
+
+                    let public_blue_work = consensus
+                        .get_virtual_parents()
+                        .into_iter()
+                        .filter_map(|p| consensus.ghostdag_primary_store.get_data(p).ok())
+                        .map(|d| d.blue_work)
+                        .max()
+                        .unwrap_or_default();
+
+                    let block_work = self.block_work.unwrap_or(1);
+                    if state.should_release(public_blue_work, block_work) {
+                        debug!("attacker coalition releasing {} withheld blocks at t={now}", state.withheld.len());
+                        for released in state.release(hash, public_blue_work) {
+                            let released_hash = released.hash();
+                            Self::insert_block(&consensus, released);
+                            Self::sample_block_work(&mut self.block_work, &consensus, released_hash);
+                        }
+                        // Re-root the now-empty private branch at the public tip the release just
+                        // landed on, so the next attacker block has a valid parent to mine on.
+                        let new_root = Self::selected_tip(&consensus).unwrap_or(hash);
+                        let new_root_blue_work =
+                            consensus.ghostdag_primary_store.get_data(new_root).map(|d| d.blue_work).unwrap_or_default();
+                        let state = miner.attacker_state.as_mut().unwrap();
+                        state.private_tips = vec![new_root];
+                        state.root_blue_work = new_root_blue_work;
+                    }
+                }
+            }
+            let hashrate_fraction = miner.hashrate_fraction;
+
+            self.record_reorg_if_any(&consensus, &mut selected_tip);
+
+            let next_wait = now + (exp.sample(&mut self.rng) * 1000.0 / hashrate_fraction) as u64;
+            heap.push(Reverse((next_wait, miner_id)));
+        }
+
+        // Force a release of any remaining withheld blocks so the attack's effect on the final
+        // DAG is always observable in `print_stats`, even if the run ends mid-withholding.
+        for miner in &mut self.miners {
+            if let Some(state) = miner.attacker_state.as_mut() {
+                if !state.withheld.is_empty() {
+                    info!("forcing release of {} remaining withheld blocks at end of run", state.withheld.len());
+                    let root = state.private_tips[0];
+                    for released in state.release(root, state.root_blue_work) {
+                        Self::insert_block(&consensus, released);
+                    }
+                }
+            }
+        }
+        self.record_reorg_if_any(&consensus, &mut selected_tip);
+
+        (consensus, handles, self.lifetime.take().expect("init must be called before run"))
+    }
+
+    /// The current selected tip, taken as the virtual parent with the highest blue work -- the
+    /// same rule virtual itself uses to pick its selected parent among its tips.
+    fn selected_tip(consensus: &Consensus) -> Option<Hash> {
+        consensus
+            .get_virtual_parents()
+            .into_iter()
+            .filter_map(|p| consensus.ghostdag_primary_store.get_data(p).ok().map(|d| (p, d.blue_work)))
+            .max_by_key(|&(_, blue_work)| blue_work)
+            .map(|(hash, _)| hash)
+    }
+
+    /// Recomputes the selected tip and, if it moved to a hash that isn't a descendant of
+    /// `*prev_tip` on the selected-parent chain, records the depth of the rollback via
+    /// [`ProcessingCounters::record_reorg`]. Driven from the simulator's event loop after each
+    /// mined or released block; `virtual_processor` does not call `record_reorg` itself.
+    fn record_reorg_if_any(&self, consensus: &Consensus, prev_tip: &mut Option<Hash>) {
+        let new_tip = Self::selected_tip(consensus);
+        if let (Some(prev), Some(new)) = (*prev_tip, new_tip) {
+            if prev != new {
+                let depth = self.selected_chain_reorg_depth(consensus, prev, new);
+                self.processing_counters.record_reorg(depth);
+            }
+        }
+        *prev_tip = new_tip;
+    }
+
+    /// Walks the selected-parent chains of `old_tip` and `new_tip` back to their common ancestor
+    /// and returns the number of blocks rolled back off `old_tip`'s chain. Returns 0 if `new_tip`
+    /// simply extends `old_tip`'s chain. The two chains are extended one hop at a time in lockstep
+    /// and checked for a meeting point after every hop, so a shallow (the common case) reorg
+    /// short-circuits after a few hops instead of first walking either chain out to the full
+    /// `MAX_REORG_SEARCH_DEPTH` bound, which exists only to cap pathological cases.
+    fn selected_chain_reorg_depth(&self, consensus: &Consensus, old_tip: Hash, new_tip: Hash) -> u64 {
+        const MAX_REORG_SEARCH_DEPTH: usize = 10_000;
+
+        let mut old_chain = vec![old_tip];
+        let mut new_chain = vec![new_tip];
+
+        for _ in 0..MAX_REORG_SEARCH_DEPTH {
+            let old_last = *old_chain.last().unwrap();
+            let new_last = *new_chain.last().unwrap();
+
+            // A match found via `new_last` sits at its own index in `old_chain`; a match found via
+            // `old_last` is `old_last` itself, whose index in `old_chain` is its own (last) index.
+            if let Some(depth) = old_chain.iter().position(|&h| h == new_last) {
+                return depth as u64;
+            }
+            if new_chain.iter().any(|&h| h == old_last) {
+                return (old_chain.len() - 1) as u64;
+            }
+
+            let old_advanced = match consensus.ghostdag_primary_store.get_data(old_last) {
+                Ok(data) => {
+                    old_chain.push(data.selected_parent);
+                    true
+                }
+                Err(_) => false,
+            };
+            let new_advanced = match consensus.ghostdag_primary_store.get_data(new_last) {
+                Ok(data) => {
+                    new_chain.push(data.selected_parent);
+                    true
+                }
+                Err(_) => false,
+            };
+            if !old_advanced && !new_advanced {
+                break;
+            }
+        }
+        old_chain.len() as u64
+    }
+
+    /// Learns this run's real per-block GHOSTDAG blue-work delta from `hash`'s ghostdag data
+    /// versus its selected parent's, the first time such a pair is available, caching it into
+    /// `cached` -- a no-op once a sample has already been taken, since every block mined in this
+    /// run shares the same (PoW-skipped) difficulty and so adds the same delta. Takes `cached` by
+    /// reference rather than `&mut self` so callers can hold it disjoint from other `&mut self`
+    /// borrows (e.g. a mutably-borrowed `Miner`).
+    fn sample_block_work(cached: &mut Option<u128>, consensus: &Consensus, hash: Hash) {
+        if cached.is_some() {
+            return;
+        }
+        if let Ok(data) = consensus.ghostdag_primary_store.get_data(hash) {
+            if let Ok(parent_data) = consensus.ghostdag_primary_store.get_data(data.selected_parent) {
+                *cached = Some(data.blue_work - parent_data.blue_work);
+            }
+        }
+    }
+
+    fn mine_block(&self, consensus: &Consensus, parents: Vec<Hash>, timestamp: u64) -> Block {
+        let header = Header::new_finalized(
+            self.config.params.version(0),
+            vec![parents],
+            Default::default(),
+            timestamp,
+            self.config.params.genesis.bits,
+            0,
+            0,
+            0.into(),
+            0,
+            Default::default(),
+        );
+        MutableBlock::new(header, vec![consensus.get_coinbase_transaction(self.target_txs_per_block)]).to_immutable()
+    }
+
+    /// Submits `block` and blocks the calling thread until virtual has resolved around it, so
+    /// that `get_virtual_parents`/`ghostdag_primary_store` reads taken right after this call (e.g.
+    /// in `record_reorg_if_any`) observe its effect instead of racing the async resolution.
+    fn insert_block(consensus: &Consensus, block: Block) {
+        let task = consensus.validate_and_insert_block(block).virtual_state_task;
+        block_on(task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_not_release_below_lead_and_cap() {
+        let mut state = AttackerState::new(Hash::default(), 10, 10);
+        state.withheld.push(dummy_block());
+        // private_blue_work(1) == 11, public is still behind by more than one block's work and
+        // nowhere near the withhold cap.
+        assert!(!state.should_release(0, 1));
+    }
+
+    #[test]
+    fn should_release_when_public_catches_up() {
+        let mut state = AttackerState::new(Hash::default(), 0, 10);
+        state.withheld.push(dummy_block());
+        // private_blue_work(1) == 1, so public_blue_work + 1 >= 1 as soon as public work reaches 0.
+        assert!(state.should_release(0, 1));
+    }
+
+    #[test]
+    fn should_release_honors_non_unit_block_work() {
+        let mut state = AttackerState::new(Hash::default(), 0, 10);
+        state.withheld.push(dummy_block());
+        state.withheld.push(dummy_block());
+        // private_blue_work(5) == 10, so a 1-unit-per-block assumption (private work 2) would have
+        // released too early here (4 + 1 >= 2) while the real lead has not been caught up (4 + 5 < 10).
+        assert!(!state.should_release(4, 5));
+        assert!(state.should_release(5, 5));
+    }
+
+    #[test]
+    fn should_release_at_withhold_cap_regardless_of_lead() {
+        let mut state = AttackerState::new(Hash::default(), 100, 2);
+        state.withheld.push(dummy_block());
+        state.withheld.push(dummy_block());
+        // Withheld count reached withhold_limit even though the private lead is enormous.
+        assert!(state.should_release(0, 1));
+    }
+
+    #[test]
+    fn release_drains_withheld_and_re_roots() {
+        let mut state = AttackerState::new(Hash::default(), 0, 10);
+        state.withheld.push(dummy_block());
+        state.withheld.push(dummy_block());
+
+        let new_root = Hash::default();
+        let released = state.release(new_root, 7);
+
+        assert_eq!(released.len(), 2);
+        assert!(state.withheld.is_empty());
+        assert_eq!(state.private_tips, vec![new_root]);
+        assert_eq!(state.root_blue_work, 7);
+        assert_eq!(state.private_blue_work(1), 7);
+    }
+
+    fn dummy_block() -> Block {
+        let header = Header::new_finalized(0, vec![vec![Hash::default()]], Default::default(), 0, 0, 0, 0, 0.into(), 0, Default::default());
+        MutableBlock::new(header, Vec::new()).to_immutable()
+    }
+}